@@ -3,46 +3,109 @@
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
-use http::uri::InvalidUri;
+use http::{uri::InvalidUri, StatusCode};
 use ruma_api::Error as RumaApiError;
+use serde::Deserialize;
 use serde_json::Error as SerdeJsonError;
 use serde_urlencoded::ser::Error as SerdeUrlEncodedSerializeError;
 
+use crate::uiaa::UiaaInfo;
+
 /// An error that can occur during client operations.
 #[derive(Debug)]
 pub struct Error(pub(crate) InnerError);
 
-impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        let message = match self.0 {
-            InnerError::AuthenticationRequired => "The queried endpoint requires authentication but was called with an anonymous client.",
-            InnerError::HttpRequester => "An HTTP error occurred.",
-            InnerError::Uri(_) => "Provided string could not be converted into a URI.",
-            InnerError::RumaApi(_) => "An error occurred converting between ruma_client_api and hyper types.",
-            InnerError::SerdeJson(_) => "A serialization error occurred.",
-            InnerError::SerdeUrlEncodedSerialize(_) => "An error occurred serializing data to a query string.",
+impl Error {
+    /// If the homeserver responded with a structured Matrix error, returns it.
+    ///
+    /// This lets callers match on well-known `errcode`s such as `M_FORBIDDEN` or
+    /// `M_UNKNOWN_TOKEN` without having to downcast a generic transport error.
+    pub fn as_server_error(&self) -> Option<&ServerError> {
+        match &self.0 {
+            InnerError::Server(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Builds an error from a non-2xx homeserver response, decoding the standard Matrix error
+    /// body (`errcode` / `error`, and `retry_after_ms` for `M_LIMIT_EXCEEDED`) if present, along
+    /// with the User-Interactive Authentication info carried by a 401 response, if any.
+    pub(crate) fn from_server_response(status_code: StatusCode, body: &[u8]) -> Self {
+        let (errcode, error, retry_after_ms) = match serde_json::from_slice::<MatrixErrorBody>(body) {
+            Ok(body) => (body.errcode, body.error, body.retry_after_ms),
+            Err(_) => ("M_UNKNOWN".to_owned(), String::from_utf8_lossy(body).into_owned(), None),
         };
 
-        write!(f, "{}", message)
+        let uiaa_info = if status_code == StatusCode::UNAUTHORIZED {
+            serde_json::from_slice::<UiaaInfo>(body).ok()
+        } else {
+            None
+        };
+
+        Self(InnerError::Server(ServerError {
+            status_code,
+            errcode,
+            error,
+            retry_after_ms,
+            uiaa_info,
+        }))
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.0 {
+            InnerError::AuthenticationRequired => write!(f, "The queried endpoint requires authentication but was called with an anonymous client."),
+            InnerError::Http(error) => write!(f, "An HTTP error occurred: {}", error),
+            InnerError::Timeout => write!(f, "The request timed out."),
+            InnerError::Server(error) => write!(f, "{}", error),
+            InnerError::Uri(_) => write!(f, "Provided string could not be converted into a URI."),
+            InnerError::RumaApi(_) => write!(f, "An error occurred converting between ruma_client_api and hyper types."),
+            InnerError::SerdeJson(_) => write!(f, "A serialization error occurred."),
+            InnerError::SerdeUrlEncodedSerialize(_) => write!(f, "An error occurred serializing data to a query string."),
+        }
     }
 }
 
 impl StdError for Error {}
 
-/// An error that can occur in the HttpRequester.
-#[derive(Debug, Copy)]
-pub struct HttpRequesterError;
-impl Display for HttpRequesterError {
+/// A structured error response returned by a Matrix homeserver for a non-2xx request.
+///
+/// Unlike a generic transport failure, this carries the HTTP status code alongside the
+/// machine-readable `errcode` (e.g. `M_FORBIDDEN`, `M_UNKNOWN_TOKEN`, `M_LIMIT_EXCEEDED`) and
+/// human-readable message from the response body, so callers can react to specific Matrix
+/// errors instead of only the HTTP status.
+#[derive(Clone, Debug)]
+pub struct ServerError {
+    /// The HTTP status code of the response.
+    pub status_code: StatusCode,
+    /// The machine-readable error code, e.g. `M_FORBIDDEN`.
+    pub errcode: String,
+    /// The human-readable error message.
+    pub error: String,
+    /// For `M_LIMIT_EXCEEDED` errors, how long the server asked us to wait before retrying.
+    pub retry_after_ms: Option<u64>,
+    /// If this was a 401 response requesting (further) User-Interactive Authentication, the
+    /// decoded UIAA info describing the available flows and session id.
+    pub uiaa_info: Option<UiaaInfo>,
+}
+
+impl Display for ServerError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "{}", "HttpRequesterError")
+        write!(f, "{} {}: {}", self.status_code, self.errcode, self.error)
     }
 }
 
-impl StdError for HttpRequesterError {}
-impl Clone for HttpRequesterError {
-    fn clone(&self) -> Self {
-        Self {}
-    }
+impl StdError for ServerError {}
+
+/// The standard Matrix error response body, as returned alongside non-2xx statuses.
+#[derive(Debug, Deserialize)]
+struct MatrixErrorBody {
+    errcode: String,
+    #[serde(default)]
+    error: String,
+    /// Present on `M_LIMIT_EXCEEDED` responses.
+    retry_after_ms: Option<u64>,
 }
 
 /// Internal representation of errors.
@@ -50,8 +113,12 @@ impl Clone for HttpRequesterError {
 pub(crate) enum InnerError {
     /// Queried endpoint requires authentication but was called on an anonymous client.
     AuthenticationRequired,
-    /// An error at the HTTP layer.
-    HttpRequester,
+    /// An error at the HTTP transport layer (a dropped connection, a DNS failure...).
+    Http(Box<dyn StdError + Send + Sync>),
+    /// The configured per-request timeout elapsed before the homeserver responded.
+    Timeout,
+    /// The homeserver responded with a non-2xx status and a structured Matrix error body.
+    Server(ServerError),
     /// An error when parsing a string as a URI.
     Uri(InvalidUri),
     /// An error converting between ruma_client_api types and Hyper types.
@@ -62,12 +129,6 @@ pub(crate) enum InnerError {
     SerdeUrlEncodedSerialize(SerdeUrlEncodedSerializeError),
 }
 
-impl From<HttpRequesterError> for Error {
-    fn from(_error: HttpRequesterError) -> Self {
-        Self(InnerError::HttpRequester)
-    }
-}
-
 impl From<InvalidUri> for Error {
     fn from(error: InvalidUri) -> Self {
         Self(InnerError::Uri(error))
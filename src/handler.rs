@@ -0,0 +1,120 @@
+//! Event handler registration for [`Client::sync_forever`](crate::Client::sync_forever).
+
+use std::{collections::HashMap, pin::Pin, sync::Arc};
+
+use futures_core::future::Future;
+use http::Request as HttpRequest;
+use ruma_events::EventType;
+use ruma_identifiers::RoomId;
+use serde_json::Value as JsonValue;
+use tower_service::Service;
+
+use crate::Client;
+
+/// A boxed, type-erased future returned by an event handler.
+type HandlerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A registered callback for a given `EventType`.
+///
+/// Handlers receive a clone of the `Client` they were registered on (so they can make further
+/// requests, e.g. to reply to a message), the room the event occurred in (`None` for
+/// account-wide events such as presence), and the raw decoded event.
+///
+/// Naming `Client<C>` requires `C: Service<HttpRequest<Vec<u8>>>`, so every item that names this
+/// alias must restate that bound itself — a bound on a type alias is not enforced by the
+/// compiler, only one on the item using it.
+pub(crate) type EventHandler<C> =
+    Arc<dyn Fn(Client<C>, Option<RoomId>, JsonValue) -> HandlerFuture + Send + Sync>;
+
+/// Stores the event handlers registered on a `Client`, keyed by event type.
+pub(crate) struct EventHandlers<C>
+where
+    C: Service<HttpRequest<Vec<u8>>>,
+{
+    by_type: HashMap<EventType, Vec<EventHandler<C>>>,
+}
+
+impl<C> EventHandlers<C>
+where
+    C: Service<HttpRequest<Vec<u8>>>,
+{
+    pub(crate) fn add(&mut self, event_type: EventType, handler: EventHandler<C>) {
+        self.by_type
+            .entry(event_type)
+            .or_insert_with(Vec::new)
+            .push(handler);
+    }
+
+    /// Returns a snapshot of the handlers registered for `event_type`.
+    ///
+    /// Cloning the `Arc`s out lets callers drop the lock on the registry before awaiting each
+    /// handler, rather than holding it across `.await`.
+    pub(crate) fn handlers_for(&self, event_type: &EventType) -> Vec<EventHandler<C>> {
+        self.by_type
+            .get(event_type)
+            .map(|handlers| handlers.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl<C> Default for EventHandlers<C>
+where
+    C: Service<HttpRequest<Vec<u8>>>,
+{
+    fn default() -> Self {
+        Self {
+            by_type: HashMap::new(),
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for EventHandlers<C>
+where
+    C: Service<HttpRequest<Vec<u8>>>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventHandlers")
+            .field("event_types", &self.by_type.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Pulls the `type` field and full JSON body out of a serializable raw event.
+///
+/// Returns `None` for malformed events, which are skipped rather than aborting the sync loop.
+pub(crate) fn decode_event<T: serde::Serialize>(raw_event: &T) -> Option<(EventType, JsonValue)> {
+    let event = serde_json::to_value(raw_event).ok()?;
+    let type_str = event.get("type")?.as_str()?.to_owned();
+    Some((EventType::from(type_str), event))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn decode_event_extracts_event_type_and_full_body() {
+        let raw_event = json!({"type": "m.room.message", "content": {"body": "hi"}});
+
+        let (event_type, decoded) = decode_event(&raw_event).unwrap();
+
+        assert_eq!(event_type, EventType::RoomMessage);
+        assert_eq!(decoded, raw_event);
+    }
+
+    #[test]
+    fn decode_event_returns_none_without_a_type_field() {
+        let raw_event = json!({"content": {"body": "hi"}});
+
+        assert!(decode_event(&raw_event).is_none());
+    }
+
+    #[test]
+    fn decode_event_returns_none_when_type_is_not_a_string() {
+        let raw_event = json!({"type": 42});
+
+        assert!(decode_event(&raw_event).is_none());
+    }
+}
@@ -1,5 +1,15 @@
-pub use crate::{
-    error::{Error, HttpRequesterError},
+//! A `Client` backed by `hyper`, with a builder for configuring proxying, timeouts, the
+//! `User-Agent` header, and (for `HttpsClient`) TLS certificate verification.
+
+pub use crate::error::Error;
+
+use std::{
+    sync::{atomic::AtomicU64, Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    error::InnerError, handler::EventHandlers, Client, ClientData, RetryConfig, Session,
 };
 
 use hyper::error::Error as HyperError;
@@ -7,41 +17,184 @@ use http::Response as HttpResponse;
 use hyper::{client::HttpConnector, Client as HyperClient, Uri};
 #[cfg(feature = "hyper-tls")]
 use hyper_tls::HttpsConnector;
+use hyper_proxy::{Intercept, Proxy, ProxyConnector};
 use std::pin::Pin;
+use url::Url;
 
 
 impl From<HyperError> for Error {
-    fn from(_error: HyperError) -> Self {
-        Self(InnerError::HttpRequesterError)
+    fn from(error: HyperError) -> Self {
+        Self(InnerError::Http(Box::new(error)))
+    }
+}
 
 /// Non-secured variant of the client (using plain HTTP requests)
-pub type HttpClient = Client<HttpConnector>;
+pub type HttpClient = Client<ProxyConnector<HttpConnector>>;
 
 impl HttpClient {
     /// Creates a new client for making HTTP requests to the given homeserver.
     pub fn new(homeserver_url: Url, session: Option<Session>) -> Self {
+        Self::with_config(homeserver_url, session, ClientConfig::default())
+    }
+
+    /// Creates a new client for making HTTP requests to the given homeserver, using `config` to
+    /// set up proxying, the `User-Agent` header, and the per-request timeout.
+    pub fn with_config(homeserver_url: Url, session: Option<Session>, config: ClientConfig) -> Self {
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        let connector = wrap_proxy(connector, &config);
+
         Self(Arc::new(ClientData {
             homeserver_url,
-            http_client: HyperClient::builder().keep_alive(true).build_http(),
+            http_client: HyperClient::builder().keep_alive(true).build(connector),
             session: Mutex::new(session),
+            retry_config: Mutex::new(RetryConfig::default()),
+            event_handlers: Mutex::new(EventHandlers::default()),
+            user_agent: Mutex::new(config.user_agent.clone()),
+            request_timeout: Mutex::new(config.request_timeout),
+            txn_id_counter: AtomicU64::new(0),
         }))
     }
 }
 
 /// Secured variant of the client (using HTTPS requests)
 #[cfg(feature = "tls")]
-pub type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+pub type HttpsClient = Client<ProxyConnector<HttpsConnector<HttpConnector>>>;
 
 #[cfg(feature = "tls")]
 impl HttpsClient {
     /// Creates a new client for making HTTPS requests to the given homeserver.
     pub fn https(homeserver_url: Url, session: Option<Session>) -> Self {
-        let connector = HttpsConnector::new();
+        Self::https_with_config(homeserver_url, session, ClientConfig::default())
+    }
+
+    /// Creates a new client for making HTTPS requests to the given homeserver, using `config`
+    /// to set up proxying, the `User-Agent` header, the per-request timeout, and (for testing
+    /// against a homeserver with a self-signed certificate) whether to skip TLS certificate
+    /// verification.
+    pub fn https_with_config(
+        homeserver_url: Url,
+        session: Option<Session>,
+        config: ClientConfig,
+    ) -> Self {
+        let tls = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(config.danger_accept_invalid_certs)
+            .build()
+            .expect("failed to build TLS connector");
+        let mut http_connector = HttpConnector::new();
+        http_connector.enforce_http(false);
+        let https_connector = HttpsConnector::from((http_connector, tls.into()));
+        let connector = wrap_proxy(https_connector, &config);
 
         Self(Arc::new(ClientData {
             homeserver_url,
             http_client: HyperClient::builder().keep_alive(true).build(connector),
             session: Mutex::new(session),
+            retry_config: Mutex::new(RetryConfig::default()),
+            event_handlers: Mutex::new(EventHandlers::default()),
+            user_agent: Mutex::new(config.user_agent.clone()),
+            request_timeout: Mutex::new(config.request_timeout),
+            txn_id_counter: AtomicU64::new(0),
         }))
     }
 }
+
+/// Wraps `connector` in a `ProxyConnector`, routing requests through `config.proxy` if set, or
+/// otherwise leaving every connection untouched.
+fn wrap_proxy<C>(connector: C, config: &ClientConfig) -> ProxyConnector<C>
+where
+    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+{
+    let mut proxy_connector =
+        ProxyConnector::new(connector).expect("failed to construct proxy connector");
+
+    if let Some(proxy_uri) = &config.proxy {
+        proxy_connector.add_proxy(Proxy::new(Intercept::All, proxy_uri.clone()));
+    }
+
+    proxy_connector
+}
+
+/// Configuration for an `HttpClient`/`HttpsClient`'s connection behavior: proxying, the
+/// `User-Agent` header, the per-request timeout, and (for `HttpsClient`) TLS certificate
+/// verification.
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// The `User-Agent` header value attached to every request.
+    pub user_agent: String,
+    /// How long to wait for a single request to complete before giving up.
+    pub request_timeout: Duration,
+    /// An HTTP proxy to route requests through, if any. SOCKS proxies are not supported.
+    pub proxy: Option<Uri>,
+    /// Whether to skip TLS certificate verification. Only used by `HttpsClient`; intended for
+    /// testing against homeservers with self-signed certificates, never for production use.
+    pub danger_accept_invalid_certs: bool,
+}
+
+impl ClientConfig {
+    /// Sets the `User-Agent` header value attached to every request.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Sets the per-request timeout.
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Routes requests through the given HTTP proxy. SOCKS proxies are not supported.
+    pub fn proxy(mut self, proxy: Uri) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Disables TLS certificate verification. Only meaningful for `HttpsClient`; useful for
+    /// testing against a homeserver with a self-signed certificate, never for production use.
+    pub fn danger_accept_invalid_certs(mut self, danger_accept_invalid_certs: bool) -> Self {
+        self.danger_accept_invalid_certs = danger_accept_invalid_certs;
+        self
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: concat!("ruma-client/", env!("CARGO_PKG_VERSION")).to_owned(),
+            request_timeout: Duration::from_secs(30),
+            proxy: None,
+            danger_accept_invalid_certs: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_has_no_proxy_and_verifies_certs() {
+        let config = ClientConfig::default();
+
+        assert_eq!(config.request_timeout, Duration::from_secs(30));
+        assert!(config.proxy.is_none());
+        assert!(!config.danger_accept_invalid_certs);
+    }
+
+    #[test]
+    fn builder_methods_override_defaults_without_affecting_each_other() {
+        let proxy: Uri = "http://proxy.example:8080".parse().unwrap();
+
+        let config = ClientConfig::default()
+            .user_agent("my-bot/1.0")
+            .request_timeout(Duration::from_secs(5))
+            .proxy(proxy.clone())
+            .danger_accept_invalid_certs(true);
+
+        assert_eq!(config.user_agent, "my-bot/1.0");
+        assert_eq!(config.request_timeout, Duration::from_secs(5));
+        assert_eq!(config.proxy, Some(proxy));
+        assert!(config.danger_accept_invalid_certs);
+    }
+}
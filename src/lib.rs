@@ -85,7 +85,11 @@
 use std::{
     convert::TryFrom,
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use futures_core::{
@@ -94,21 +98,23 @@ use futures_core::{
 };
 use tower_service::Service;
 use futures_util::stream;
-use http::{Request as HttpRequest, Response as HttpResponse, Uri};
+use http::{Request as HttpRequest, Response as HttpResponse, StatusCode, Uri};
 use ruma_api::{Endpoint, Outgoing};
 use url::Url;
 
 use crate::error::InnerError;
 
 #[cfg(feature = "hyper_client")]
-pub use hyper_client::HttpClient;
+pub use hyper_client::{ClientConfig, HttpClient};
 #[cfg(feature = "tls")]
 pub use hyper_client::HttpsClient;
 
 
 pub use crate::{
-    error::Error,
+    error::{Error, ServerError},
+    retry::RetryConfig,
     session::Session,
+    uiaa::{AuthData, UiaaFlow, UiaaInfo, UiaaOutcome},
 };
 pub use ruma_client_api as api;
 pub use ruma_events as events;
@@ -117,7 +123,12 @@ pub use ruma_identifiers as identifiers;
 /// Matrix client-server API endpoints.
 //pub mod api;
 mod error;
+mod handler;
+#[cfg(any(feature = "hyper_client", feature = "tls"))]
+mod hyper_client;
+mod retry;
 mod session;
+mod uiaa;
 
 /// A client for the Matrix client-server API.
 #[derive(Debug)]
@@ -135,6 +146,16 @@ where
     http_client: C,
     /// User session data.
     session: Mutex<Option<Session>>,
+    /// Configuration for the automatic retrying of failed requests.
+    retry_config: Mutex<RetryConfig>,
+    /// Event handlers registered via `Client::add_event_handler`.
+    event_handlers: Mutex<handler::EventHandlers<C>>,
+    /// The `User-Agent` header value attached to every request.
+    user_agent: Mutex<String>,
+    /// How long to wait for a single request to complete before giving up.
+    request_timeout: Mutex<Duration>,
+    /// A per-client counter used to generate transaction ids for `send_message_event`.
+    txn_id_counter: AtomicU64,
 }
 
 trait TypeEquals {
@@ -165,6 +186,11 @@ where
             homeserver_url,
             http_client: http_client,
             session: Mutex::new(session),
+            retry_config: Mutex::new(RetryConfig::default()),
+            event_handlers: Mutex::new(handler::EventHandlers::default()),
+            user_agent: Mutex::new(concat!("ruma-client/", env!("CARGO_PKG_VERSION")).to_owned()),
+            request_timeout: Mutex::new(Duration::from_secs(30)),
+            txn_id_counter: AtomicU64::new(0),
         }))
     }
 
@@ -179,6 +205,71 @@ where
             .clone()
     }
 
+    /// Get a copy of the current retry configuration.
+    pub fn retry_config(&self) -> RetryConfig {
+        *self
+            .0
+            .retry_config
+            .lock()
+            .expect("retry config mutex was poisoned")
+    }
+
+    /// Override the retry configuration used for subsequent requests, e.g. to disable automatic
+    /// retries or tune the backoff parameters.
+    pub fn set_retry_config(&self, retry_config: RetryConfig) {
+        *self
+            .0
+            .retry_config
+            .lock()
+            .expect("retry config mutex was poisoned") = retry_config;
+    }
+
+    /// Get a copy of the current `User-Agent` header value attached to every request.
+    pub fn user_agent(&self) -> String {
+        self.0
+            .user_agent
+            .lock()
+            .expect("user agent mutex was poisoned")
+            .clone()
+    }
+
+    /// Override the `User-Agent` header value attached to every request.
+    pub fn set_user_agent(&self, user_agent: impl Into<String>) {
+        *self
+            .0
+            .user_agent
+            .lock()
+            .expect("user agent mutex was poisoned") = user_agent.into();
+    }
+
+    /// Get the current per-request timeout.
+    pub fn request_timeout(&self) -> Duration {
+        *self
+            .0
+            .request_timeout
+            .lock()
+            .expect("request timeout mutex was poisoned")
+    }
+
+    /// Override the per-request timeout used for subsequent requests.
+    pub fn set_request_timeout(&self, request_timeout: Duration) {
+        *self
+            .0
+            .request_timeout
+            .lock()
+            .expect("request timeout mutex was poisoned") = request_timeout;
+    }
+
+    /// Generates the next transaction id for this client.
+    ///
+    /// Transaction ids only need to be unique per access token, so a per-client counter is
+    /// enough; [`send_message_event`](Client::send_message_event) uses this to guarantee that a
+    /// request retried by the automatic retry subsystem reuses the same id rather than risking
+    /// the homeserver seeing it as two separate messages.
+    pub fn next_txn_id(&self) -> String {
+        self.0.txn_id_counter.fetch_add(1, Ordering::SeqCst).to_string()
+    }
+
     /// Log in with a username and password.
     ///
     /// In contrast to `api::r0::session::login::call()`, this method stores the
@@ -216,29 +307,40 @@ where
     /// Register as a guest. In contrast to `api::r0::account::register::call()`,
     /// this method stores the session data returned by the endpoint in this
     /// client, instead of returning it.
-    pub async fn register_guest(&self) -> Result<Session, Error> {
-        use api::r0::account::register;
+    ///
+    /// Returns [`UiaaOutcome::NeedsAuth`] if the homeserver requires User-Interactive
+    /// Authentication before it will complete registration; retry with
+    /// [`register_guest_with_auth`](Client::register_guest_with_auth), carrying the session id
+    /// from the returned [`UiaaInfo`].
+    pub async fn register_guest(&self) -> Result<UiaaOutcome<Session>, Error> {
+        self.register_guest_with_auth_data(None).await
+    }
 
-        let response = self
-            .request(register::Request {
-                auth: None,
-                bind_email: None,
-                device_id: None,
-                initial_device_display_name: None,
-                kind: Some(register::RegistrationKind::Guest),
-                password: None,
-                username: None,
-            })
-            .await?;
+    /// Like [`register_guest`](Client::register_guest), but supplies `auth` to complete one
+    /// stage of a User-Interactive Authentication flow.
+    pub async fn register_guest_with_auth(
+        &self,
+        auth: AuthData,
+    ) -> Result<UiaaOutcome<Session>, Error> {
+        self.register_guest_with_auth_data(Some(auth)).await
+    }
 
-        let session = Session {
-            access_token: response.access_token,
-            device_id: response.device_id,
-            user_id: response.user_id,
-        };
-        *self.0.session.lock().unwrap() = Some(session.clone());
+    async fn register_guest_with_auth_data(
+        &self,
+        auth: Option<AuthData>,
+    ) -> Result<UiaaOutcome<Session>, Error> {
+        use api::r0::account::register;
 
-        Ok(session)
+        self.run_register(register::Request {
+            auth: auth.as_ref().map(AuthData::as_ruma),
+            bind_email: None,
+            device_id: None,
+            initial_device_display_name: None,
+            kind: Some(register::RegistrationKind::Guest),
+            password: None,
+            username: None,
+        })
+        .await
     }
 
     /// Register as a new user on this server.
@@ -249,33 +351,98 @@ where
     ///
     /// The username is the local part of the returned user_id. If it is
     /// omitted from this request, the server will generate one.
+    ///
+    /// Returns [`UiaaOutcome::NeedsAuth`] if the homeserver requires User-Interactive
+    /// Authentication before it will complete registration; retry with
+    /// [`register_user_with_auth`](Client::register_user_with_auth), or use
+    /// [`register_user_with_dummy_auth`](Client::register_user_with_dummy_auth) if the server's
+    /// only requirement is the `m.login.dummy` stage.
     pub async fn register_user(
         &self,
         username: Option<String>,
         password: String,
-    ) -> Result<Session, Error> {
+    ) -> Result<UiaaOutcome<Session>, Error> {
+        self.register_user_with_auth_data(username, password, None)
+            .await
+    }
+
+    /// Like [`register_user`](Client::register_user), but supplies `auth` to complete one stage
+    /// of a User-Interactive Authentication flow.
+    pub async fn register_user_with_auth(
+        &self,
+        username: Option<String>,
+        password: String,
+        auth: AuthData,
+    ) -> Result<UiaaOutcome<Session>, Error> {
+        self.register_user_with_auth_data(username, password, Some(auth))
+            .await
+    }
+
+    /// Like [`register_user`](Client::register_user), but automatically completes the
+    /// `m.login.dummy` stage when it's enough to satisfy one of the server's flows - the common
+    /// case for homeservers that don't gate registration behind anything meaningful. Any other
+    /// required stage (e.g. `m.login.recaptcha`) is still surfaced as
+    /// [`UiaaOutcome::NeedsAuth`] for the caller to handle.
+    pub async fn register_user_with_dummy_auth(
+        &self,
+        username: Option<String>,
+        password: String,
+    ) -> Result<UiaaOutcome<Session>, Error> {
+        match self
+            .register_user(username.clone(), password.clone())
+            .await?
+        {
+            UiaaOutcome::NeedsAuth(info) if info.supports_dummy_auth() => {
+                self.register_user_with_auth(username, password, AuthData::dummy(&info))
+                    .await
+            }
+            outcome => Ok(outcome),
+        }
+    }
+
+    async fn register_user_with_auth_data(
+        &self,
+        username: Option<String>,
+        password: String,
+        auth: Option<AuthData>,
+    ) -> Result<UiaaOutcome<Session>, Error> {
         use api::r0::account::register;
 
-        let response = self
-            .request(register::Request {
-                auth: None,
-                bind_email: None,
-                device_id: None,
-                initial_device_display_name: None,
-                kind: Some(register::RegistrationKind::User),
-                password: Some(password),
-                username,
-            })
-            .await?;
+        self.run_register(register::Request {
+            auth: auth.as_ref().map(AuthData::as_ruma),
+            bind_email: None,
+            device_id: None,
+            initial_device_display_name: None,
+            kind: Some(register::RegistrationKind::User),
+            password: Some(password),
+            username,
+        })
+        .await
+    }
 
-        let session = Session {
-            access_token: response.access_token,
-            device_id: response.device_id,
-            user_id: response.user_id,
-        };
-        *self.0.session.lock().unwrap() = Some(session.clone());
+    /// Runs a `register::Request`, storing the session from a successful response, or
+    /// surfacing the server's `UiaaInfo` if it demands (further) User-Interactive
+    /// Authentication instead of failing outright.
+    async fn run_register(
+        &self,
+        request: api::r0::account::register::Request<'_>,
+    ) -> Result<UiaaOutcome<Session>, Error> {
+        match self.request(request).await {
+            Ok(response) => {
+                let session = Session {
+                    access_token: response.access_token,
+                    device_id: response.device_id,
+                    user_id: response.user_id,
+                };
+                *self.0.session.lock().unwrap() = Some(session.clone());
 
-        Ok(session)
+                Ok(UiaaOutcome::Done(session))
+            }
+            Err(error) => match error.as_server_error().and_then(|e| e.uiaa_info.clone()) {
+                Some(info) => Ok(UiaaOutcome::NeedsAuth(info)),
+                None => Err(error),
+            },
+        }
     }
 
     /// Convenience method that represents repeated calls to the sync_events endpoint as a stream.
@@ -344,6 +511,135 @@ where
         })
     }
 
+    /// Registers an asynchronous callback to run for every event of the given type seen while
+    /// driving [`sync_forever`](Client::sync_forever).
+    ///
+    /// The handler is passed a clone of this `Client` (so it can make further requests, e.g. to
+    /// reply to a message it just received), the room the event occurred in (`None` for
+    /// account-wide events such as presence), and the decoded event body.
+    pub fn add_event_handler<F, Fut>(&self, event_type: events::EventType, handler: F)
+    where
+        F: Fn(Self, Option<identifiers::RoomId>, serde_json::Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.0
+            .event_handlers
+            .lock()
+            .expect("event handlers mutex was poisoned")
+            .add(
+                event_type,
+                std::sync::Arc::new(move |client, room_id, event| {
+                    Box::pin(handler(client, room_id, event)) as _
+                }),
+            );
+    }
+
+    /// Drives [`sync`](Client::sync) forever, dispatching every room timeline, room state, and
+    /// presence event in each response to the handlers registered via
+    /// [`add_event_handler`](Client::add_event_handler) before advancing to the next batch.
+    ///
+    /// This saves callers from hand-rolling the sync loop and event matching boilerplate; it's
+    /// the basis on which a bot can be built using only `add_event_handler`.
+    pub async fn sync_forever(
+        &self,
+        filter: Option<api::r0::sync::sync_events::Filter>,
+        since: Option<String>,
+        set_presence: bool,
+    ) -> Result<(), Error> {
+        use futures_util::stream::TryStreamExt as _;
+
+        let mut sync_stream = Box::pin(self.sync(filter, since, set_presence));
+
+        while let Some(response) = sync_stream.try_next().await? {
+            self.dispatch_sync_response(&response).await;
+        }
+
+        Ok(())
+    }
+
+    /// Walks the room timeline, room state, and presence events in a sync response, dispatching
+    /// each to the handlers registered for its event type.
+    async fn dispatch_sync_response(&self, response: &api::r0::sync::sync_events::IncomingResponse) {
+        for (room_id, joined_room) in &response.rooms.join {
+            for raw_event in &joined_room.timeline.events {
+                self.dispatch_raw_event(Some(room_id.clone()), raw_event).await;
+            }
+            for raw_event in &joined_room.state.events {
+                self.dispatch_raw_event(Some(room_id.clone()), raw_event).await;
+            }
+        }
+
+        for raw_event in &response.presence.events {
+            self.dispatch_raw_event(None, raw_event).await;
+        }
+    }
+
+    /// Decodes a single raw event and invokes the handlers registered for its type, if any.
+    async fn dispatch_raw_event<T: serde::Serialize>(
+        &self,
+        room_id: Option<identifiers::RoomId>,
+        raw_event: &T,
+    ) {
+        let (event_type, event) = match handler::decode_event(raw_event) {
+            Some(decoded) => decoded,
+            None => return,
+        };
+
+        let handlers = self
+            .0
+            .event_handlers
+            .lock()
+            .expect("event handlers mutex was poisoned")
+            .handlers_for(&event_type);
+
+        for handler in handlers {
+            handler(self.clone(), room_id.clone(), event.clone()).await;
+        }
+    }
+
+    /// Sends a message event to the given room, filling in a transaction id automatically.
+    ///
+    /// Use [`send_message_event_with_txn_id`](Client::send_message_event_with_txn_id) if you
+    /// persist your own transaction ids (e.g. to guarantee idempotency across process
+    /// restarts).
+    pub fn send_message_event<Content>(
+        &self,
+        room_id: identifiers::RoomId,
+        content: Content,
+    ) -> impl Future<Output = Result<api::r0::message::create_message_event::IncomingResponse, Error>>
+    where
+        Content: ruma_events::EventContent,
+    {
+        self.send_message_event_with_txn_id(room_id, content, self.next_txn_id())
+    }
+
+    /// Like [`send_message_event`](Client::send_message_event), but with an explicit
+    /// transaction id.
+    ///
+    /// The same `txn_id` must be reused for every retry of the same logical send - which is
+    /// exactly what happens here, since `request` only generates and serializes the outgoing
+    /// HTTP request once and replays that same request for every retry attempt.
+    pub fn send_message_event_with_txn_id<Content>(
+        &self,
+        room_id: identifiers::RoomId,
+        content: Content,
+        txn_id: String,
+    ) -> impl Future<Output = Result<api::r0::message::create_message_event::IncomingResponse, Error>>
+    where
+        Content: ruma_events::EventContent,
+    {
+        use api::r0::message::create_message_event;
+
+        let event_type = content.event_type();
+
+        self.request(create_message_event::Request {
+            room_id,
+            event_type,
+            txn_id,
+            data: content,
+        })
+    }
+
     /// Makes a request to a Matrix API endpoint.
     pub fn request<Request: Endpoint>(
         &self,
@@ -379,8 +675,92 @@ where
             }
             *request.uri_mut() = Uri::from_str(url.as_ref())?;
 
-            // Do the actual async request
-            let response = client.http_client.call(request).await?;
+            let user_agent = client.user_agent.lock().unwrap().clone();
+            if let Ok(user_agent) = http::HeaderValue::from_str(&user_agent) {
+                request.headers_mut().insert(http::header::USER_AGENT, user_agent);
+            }
+
+            let request_timeout = *client.request_timeout.lock().unwrap();
+            let retry_config = *client.retry_config.lock().unwrap();
+            let is_idempotent = retry::is_idempotent(request.method());
+            let started_at = Instant::now();
+            let mut attempt: u32 = 0;
+
+            // `http::Request` isn't `Clone` (its `Extensions` aren't), so pull out the pieces we
+            // need to replay the request and rebuild a fresh `http::Request` on every attempt
+            // instead of cloning the original.
+            let (parts, body) = request.into_parts();
+            let method = parts.method;
+            let uri = parts.uri;
+            let version = parts.version;
+            let headers = parts.headers;
+
+            // Do the actual async request, retrying transient failures as configured.
+            let response = loop {
+                let within_budget =
+                    retry_config.enabled && started_at.elapsed() < retry_config.max_elapsed_time;
+                // 5xx responses, transport errors and timeouts are only safe to replay for
+                // idempotent requests; a 429 is retried regardless of method below.
+                let can_retry = within_budget && is_idempotent;
+
+                let mut request = HttpRequest::builder()
+                    .method(method.clone())
+                    .uri(uri.clone())
+                    .version(version)
+                    .body(body.clone())
+                    .expect("rebuilding a request from previously-valid parts cannot fail");
+                *request.headers_mut() = headers.clone();
+
+                let call = client.http_client.call(request);
+                futures_util::pin_mut!(call);
+                let timeout = retry::sleep(request_timeout);
+                futures_util::pin_mut!(timeout);
+
+                let response = match futures_util::future::select(call, timeout).await {
+                    futures_util::future::Either::Left((Ok(response), _)) => response,
+                    futures_util::future::Either::Left((Err(error), _)) => {
+                        if can_retry {
+                            retry::sleep(retry_config.backoff_delay(attempt)).await;
+                            attempt += 1;
+                            continue;
+                        }
+
+                        return Err(error.into());
+                    }
+                    futures_util::future::Either::Right(((), _)) => {
+                        if can_retry {
+                            retry::sleep(retry_config.backoff_delay(attempt)).await;
+                            attempt += 1;
+                            continue;
+                        }
+
+                        return Err(Error(InnerError::Timeout));
+                    }
+                };
+
+                if can_retry && response.status().is_server_error() {
+                    retry::sleep(retry_config.backoff_delay(attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+
+                if within_budget && response.status() == StatusCode::TOO_MANY_REQUESTS {
+                    if let Some(delay) =
+                        retry::rate_limit_delay(response.body(), retry_config.backoff_delay(attempt))
+                    {
+                        retry::sleep(delay).await;
+                        attempt += 1;
+                        continue;
+                    }
+                }
+
+                break response;
+            };
+
+            if !response.status().is_success() {
+                return Err(Error::from_server_response(response.status(), response.body()));
+            }
+
             let ruma_rep = <Request::Response as Outgoing>::Incoming::try_from(response)?;
             Ok(ruma_rep)
         }
@@ -392,3 +772,209 @@ impl<C: Service<HttpRequest<Vec<u8>>>> Clone for Client<C> {
         Self(self.0.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        convert::TryInto,
+        pin::Pin,
+        sync::{atomic::AtomicUsize, Mutex},
+        task::{Context, Poll},
+    };
+
+    use futures_executor::block_on;
+    use http::Method;
+    use ruma_api::Metadata;
+
+    use super::*;
+
+    /// A canned HTTP status/body pair returned by [`FakeService`] for one call.
+    #[derive(Clone)]
+    struct CannedResponse {
+        status: StatusCode,
+        body: Vec<u8>,
+    }
+
+    impl CannedResponse {
+        fn new(status: StatusCode, body: &str) -> Self {
+            Self { status, body: body.as_bytes().to_vec() }
+        }
+    }
+
+    /// A fake transport error, only ever constructed if a test drains more responses than it
+    /// queued.
+    #[derive(Debug)]
+    struct FakeTransportError;
+
+    impl std::fmt::Display for FakeTransportError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "fake transport ran out of canned responses")
+        }
+    }
+
+    impl std::error::Error for FakeTransportError {}
+
+    impl From<FakeTransportError> for Error {
+        fn from(error: FakeTransportError) -> Self {
+            Error(InnerError::Http(Box::new(error)))
+        }
+    }
+
+    /// A fake `Service` that returns a queue of canned responses, one per call, and counts how
+    /// many times it was called.
+    #[derive(Clone)]
+    struct FakeService {
+        responses: Arc<Mutex<VecDeque<CannedResponse>>>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl FakeService {
+        fn new(responses: Vec<CannedResponse>) -> Self {
+            Self {
+                responses: Arc::new(Mutex::new(responses.into_iter().collect())),
+                calls: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        fn call_count(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Service<HttpRequest<Vec<u8>>> for FakeService {
+        type Response = HttpResponse<Vec<u8>>;
+        type Error = FakeTransportError;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: HttpRequest<Vec<u8>>) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let canned = self.responses.lock().unwrap().pop_front();
+
+            Box::pin(async move {
+                let canned = canned.ok_or(FakeTransportError)?;
+                Ok(HttpResponse::builder()
+                    .status(canned.status)
+                    .body(canned.body)
+                    .expect("building a canned test response cannot fail"))
+            })
+        }
+    }
+
+    /// A minimal `Endpoint` whose HTTP method is fixed by the `METHOD` const generic, used to
+    /// drive `Client::request` through a fake `Service` without depending on any real
+    /// `ruma_client_api` endpoint.
+    #[derive(Debug)]
+    struct TestRequest<const METHOD_IS_POST: bool>;
+
+    impl<const METHOD_IS_POST: bool> Outgoing for TestRequest<METHOD_IS_POST> {
+        type Incoming = Self;
+    }
+
+    impl<const METHOD_IS_POST: bool> TryFrom<HttpRequest<Vec<u8>>> for TestRequest<METHOD_IS_POST> {
+        type Error = ruma_api::Error;
+
+        fn try_from(_request: HttpRequest<Vec<u8>>) -> Result<Self, Self::Error> {
+            Ok(Self)
+        }
+    }
+
+    impl<const METHOD_IS_POST: bool> TryInto<HttpRequest<Vec<u8>>> for TestRequest<METHOD_IS_POST> {
+        type Error = ruma_api::Error;
+
+        fn try_into(self) -> Result<HttpRequest<Vec<u8>>, Self::Error> {
+            let method = if METHOD_IS_POST { Method::POST } else { Method::GET };
+            Ok(HttpRequest::builder()
+                .method(method)
+                .uri("/test")
+                .body(Vec::new())
+                .expect("building a fake test request cannot fail"))
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestResponse;
+
+    impl Outgoing for TestResponse {
+        type Incoming = Self;
+    }
+
+    impl TryFrom<HttpResponse<Vec<u8>>> for TestResponse {
+        type Error = ruma_api::Error;
+
+        fn try_from(_response: HttpResponse<Vec<u8>>) -> Result<Self, Self::Error> {
+            Ok(Self)
+        }
+    }
+
+    impl Endpoint for TestRequest<false> {
+        type Response = TestResponse;
+
+        const METADATA: Metadata = Metadata {
+            description: "test GET endpoint",
+            method: Method::GET,
+            name: "test_get_endpoint",
+            path: "/test",
+            rate_limited: false,
+            requires_authentication: false,
+        };
+    }
+
+    impl Endpoint for TestRequest<true> {
+        type Response = TestResponse;
+
+        const METADATA: Metadata = Metadata {
+            description: "test POST endpoint",
+            method: Method::POST,
+            name: "test_post_endpoint",
+            path: "/test",
+            rate_limited: false,
+            requires_authentication: false,
+        };
+    }
+
+    fn fast_retry_config() -> RetryConfig {
+        RetryConfig {
+            enabled: true,
+            initial_interval: Duration::from_millis(1),
+            max_interval: Duration::from_millis(10),
+            max_elapsed_time: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn request_retries_rate_limited_idempotent_request_then_succeeds() {
+        let service = FakeService::new(vec![
+            CannedResponse::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                r#"{"errcode":"M_LIMIT_EXCEEDED","error":"too fast","retry_after_ms":1}"#,
+            ),
+            CannedResponse::new(StatusCode::OK, "{}"),
+        ]);
+        let client = Client::new(service.clone(), Url::parse("http://localhost").unwrap(), None);
+        client.set_retry_config(fast_retry_config());
+
+        block_on(client.request(TestRequest::<false>)).expect("request should eventually succeed");
+
+        assert_eq!(service.call_count(), 2);
+    }
+
+    #[test]
+    fn request_does_not_retry_server_error_on_non_idempotent_request() {
+        let service = FakeService::new(vec![CannedResponse::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            r#"{"errcode":"M_UNKNOWN","error":"boom"}"#,
+        )]);
+        let client = Client::new(service.clone(), Url::parse("http://localhost").unwrap(), None);
+        client.set_retry_config(fast_retry_config());
+
+        let result = block_on(client.request(TestRequest::<true>));
+
+        assert!(result.is_err());
+        assert_eq!(service.call_count(), 1);
+    }
+}
@@ -0,0 +1,170 @@
+//! Automatic retrying of failed requests for [`Client::request`](crate::Client::request).
+
+use std::time::Duration;
+
+use http::Method;
+use rand::Rng;
+use serde::Deserialize;
+
+/// Configuration for the automatic retrying of failed requests.
+///
+/// 5xx responses and transport-level errors are retried with exponential backoff and jitter, but
+/// only for idempotent requests (see [`is_idempotent`]) — a non-idempotent request that times out
+/// or 500s mid-flight is surfaced to the caller instead of being blindly replayed. HTTP 429
+/// responses carrying an `M_LIMIT_EXCEEDED` error are retried regardless of method, after the
+/// server-provided `retry_after_ms` if present, or the normal backoff curve otherwise. Other 4xx
+/// errors are never retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    /// Whether failed requests should be retried at all. Defaults to `true`.
+    pub enabled: bool,
+    /// The delay before the first retry attempt.
+    pub initial_interval: Duration,
+    /// The maximum delay between two successive retry attempts, before jitter is applied.
+    pub max_interval: Duration,
+    /// The maximum amount of time to keep retrying a single request before giving up and
+    /// returning the last error.
+    pub max_elapsed_time: Duration,
+}
+
+impl RetryConfig {
+    /// A configuration with automatic retries turned off.
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+
+    /// Computes the backoff delay to wait before the given retry attempt (0-indexed).
+    ///
+    /// The delay doubles with every attempt and is capped at `max_interval`, then jittered by
+    /// up to ±50% to avoid synchronized retries from multiple clients.
+    pub(crate) fn backoff_delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial_interval
+            .checked_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX))
+            .unwrap_or(self.max_interval);
+        let capped = std::cmp::min(exponential, self.max_interval);
+
+        let jitter = rand::thread_rng().gen_range(0.5..=1.5);
+        capped.mul_f64(jitter)
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(30),
+            max_elapsed_time: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Sleeps for the given duration without depending on any particular async runtime, so it can be
+/// awaited from the generic `Service` bound on `Client`.
+pub(crate) async fn sleep(duration: Duration) {
+    futures_timer::Delay::new(duration).await;
+}
+
+/// Returns `true` if a request using `method` is safe to replay automatically, i.e. repeating it
+/// has no additional effect beyond the first successful attempt.
+///
+/// POST (and other non-idempotent methods) are excluded: a timed-out or 500-ing `register`/
+/// `login` call may have already taken effect on the server, so blindly replaying it could
+/// double a side effect instead of just retrying a no-op.
+pub(crate) fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::PUT | Method::DELETE | Method::HEAD | Method::OPTIONS)
+}
+
+/// The body of a Matrix error response, as returned for rate-limited (`M_LIMIT_EXCEEDED`)
+/// requests.
+#[derive(Debug, Deserialize)]
+struct RateLimitedErrorBody {
+    errcode: String,
+    retry_after_ms: Option<u64>,
+}
+
+/// If `body` is a `M_LIMIT_EXCEEDED` error, returns the delay to wait before retrying: the
+/// server-provided `retry_after_ms` if present, or `fallback` (the normal backoff curve)
+/// otherwise, since a spec-legal `M_LIMIT_EXCEEDED` response is not required to carry a
+/// `retry_after_ms`. Returns `None` if `body` isn't a rate-limit error at all.
+pub(crate) fn rate_limit_delay(body: &[u8], fallback: Duration) -> Option<Duration> {
+    let error: RateLimitedErrorBody = serde_json::from_slice(body).ok()?;
+    if error.errcode != "M_LIMIT_EXCEEDED" {
+        return None;
+    }
+
+    Some(error.retry_after_ms.map(Duration::from_millis).unwrap_or(fallback))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unjittered_config() -> RetryConfig {
+        RetryConfig {
+            enabled: true,
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(30),
+            max_elapsed_time: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn backoff_delay_doubles_within_jitter_bounds() {
+        let config = unjittered_config();
+
+        for attempt in 0..5 {
+            let unjittered = config.initial_interval * 2u32.pow(attempt);
+            let delay = config.backoff_delay(attempt);
+            assert!(delay >= unjittered.mul_f64(0.5));
+            assert!(delay <= unjittered.mul_f64(1.5));
+        }
+    }
+
+    #[test]
+    fn backoff_delay_is_capped_at_max_interval() {
+        let config = unjittered_config();
+        let delay = config.backoff_delay(32);
+        assert!(delay <= config.max_interval.mul_f64(1.5));
+    }
+
+    #[test]
+    fn is_idempotent_accepts_get_put_delete_head_options() {
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::PUT));
+        assert!(is_idempotent(&Method::DELETE));
+        assert!(is_idempotent(&Method::HEAD));
+        assert!(is_idempotent(&Method::OPTIONS));
+    }
+
+    #[test]
+    fn is_idempotent_rejects_post_and_patch() {
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+
+    #[test]
+    fn rate_limit_delay_uses_retry_after_ms_when_present() {
+        let body = br#"{"errcode":"M_LIMIT_EXCEEDED","error":"too fast","retry_after_ms":2000}"#;
+        let delay = rate_limit_delay(body, Duration::from_secs(99));
+        assert_eq!(delay, Some(Duration::from_millis(2000)));
+    }
+
+    #[test]
+    fn rate_limit_delay_falls_back_when_retry_after_ms_is_absent() {
+        let body = br#"{"errcode":"M_LIMIT_EXCEEDED","error":"too fast"}"#;
+        let fallback = Duration::from_secs(5);
+        let delay = rate_limit_delay(body, fallback);
+        assert_eq!(delay, Some(fallback));
+    }
+
+    #[test]
+    fn rate_limit_delay_returns_none_for_other_errors() {
+        let body = br#"{"errcode":"M_FORBIDDEN","error":"nope"}"#;
+        assert_eq!(rate_limit_delay(body, Duration::from_secs(5)), None);
+    }
+}
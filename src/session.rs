@@ -0,0 +1,18 @@
+//! Types for saving and restoring a client's login session.
+
+use ruma_identifiers::UserId;
+
+/// A user session, as returned by [`Client::log_in`](crate::Client::log_in) and the
+/// registration endpoints.
+///
+/// Hold on to this (e.g. by serializing it to disk) and pass it back into a new `Client` to
+/// avoid logging in again.
+#[derive(Clone, Debug)]
+pub struct Session {
+    /// The access token used for all authenticated requests.
+    pub access_token: String,
+    /// The device ID associated with this session.
+    pub device_id: String,
+    /// The Matrix user ID of the logged-in user.
+    pub user_id: UserId,
+}
@@ -0,0 +1,158 @@
+//! User-Interactive Authentication (UIAA) support for registration and other account endpoints
+//! that may require one or more rounds of authentication before succeeding.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::api::r0::uiaa;
+
+/// The body of a 401 response requesting (further) User-Interactive Authentication, as defined
+/// by the Matrix spec.
+///
+/// Pick a flow whose `stages` you can satisfy, build the [`AuthData`] for the next stage in it
+/// that isn't already in `completed`, and resubmit the original request with that as its `auth`
+/// field, carrying `session` along unchanged across every round of the same flow.
+#[derive(Clone, Debug, Deserialize)]
+pub struct UiaaInfo {
+    /// The authentication flows the server will accept; each is a list of stages that must all
+    /// be completed, in order.
+    pub flows: Vec<UiaaFlow>,
+    /// Parameters required by some stages, e.g. the sitekey for `m.login.recaptcha`.
+    #[serde(default)]
+    pub params: BTreeMap<String, JsonValue>,
+    /// The stages already completed in this session.
+    #[serde(default)]
+    pub completed: Vec<String>,
+    /// The session ID to carry through subsequent attempts at this flow.
+    pub session: String,
+}
+
+/// A single authentication flow: a list of stages that must all be completed, in order.
+#[derive(Clone, Debug, Deserialize)]
+pub struct UiaaFlow {
+    /// The stages of this flow, e.g. `["m.login.dummy"]`.
+    pub stages: Vec<String>,
+}
+
+impl UiaaInfo {
+    /// Returns `true` if some flow the server offers can be completed using only the
+    /// `m.login.dummy` stage, i.e. every stage in it is either already `completed` or is
+    /// `m.login.dummy` itself.
+    pub fn supports_dummy_auth(&self) -> bool {
+        self.flows.iter().any(|flow| {
+            flow.stages
+                .iter()
+                .all(|stage| stage == "m.login.dummy" || self.completed.iter().any(|c| c == stage))
+        })
+    }
+}
+
+/// The `auth` field submitted alongside a request's normal parameters to complete one stage of
+/// a UIAA flow.
+///
+/// This owns the data a caller builds up (e.g. via [`AuthData::dummy`]); [`AuthData::as_ruma`]
+/// borrows it as the [`uiaa::AuthData`] that `ruma_client_api` endpoints such as `register`
+/// actually expect in their `auth` field, since that type is owned by `ruma_client_api` and
+/// borrows its fields rather than owning them.
+#[derive(Clone, Debug)]
+pub struct AuthData {
+    /// The authentication type for this stage, e.g. `m.login.dummy`.
+    kind: String,
+    /// The session ID from the server's 401 response.
+    session: String,
+    /// Any additional fields the stage requires (e.g. `response` for `m.login.recaptcha`).
+    auth_parameters: BTreeMap<String, JsonValue>,
+}
+
+impl AuthData {
+    /// Builds the `auth` data for a stage of type `kind` (e.g. `m.login.recaptcha`), carrying
+    /// the session id from `info` and any additional fields the stage requires.
+    pub fn new(
+        kind: impl Into<String>,
+        info: &UiaaInfo,
+        auth_parameters: BTreeMap<String, JsonValue>,
+    ) -> Self {
+        Self {
+            kind: kind.into(),
+            session: info.session.clone(),
+            auth_parameters,
+        }
+    }
+
+    /// Builds the `auth` data that satisfies the `m.login.dummy` stage, which the Matrix spec
+    /// defines as requiring no input beyond the session id.
+    pub fn dummy(info: &UiaaInfo) -> Self {
+        Self {
+            kind: "m.login.dummy".to_owned(),
+            session: info.session.clone(),
+            auth_parameters: BTreeMap::new(),
+        }
+    }
+
+    /// Borrows this data as the `ruma_client_api` UIAA auth type expected by the `auth` field of
+    /// endpoints such as `register`.
+    pub(crate) fn as_ruma(&self) -> uiaa::AuthData<'_> {
+        uiaa::AuthData {
+            kind: &self.kind,
+            session: Some(&self.session),
+            auth_parameters: self.auth_parameters.clone(),
+        }
+    }
+}
+
+/// The outcome of a single attempt at a User-Interactive Authentication-gated request.
+#[derive(Debug)]
+pub enum UiaaOutcome<T> {
+    /// The request succeeded.
+    Done(T),
+    /// The server requires (further) authentication. Build an [`AuthData`] for one of the
+    /// uncompleted stages in a flow from `UiaaInfo::flows` and retry with it.
+    NeedsAuth(UiaaInfo),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(flows: Vec<Vec<&str>>, completed: Vec<&str>) -> UiaaInfo {
+        UiaaInfo {
+            flows: flows
+                .into_iter()
+                .map(|stages| UiaaFlow {
+                    stages: stages.into_iter().map(str::to_owned).collect(),
+                })
+                .collect(),
+            params: BTreeMap::new(),
+            completed: completed.into_iter().map(str::to_owned).collect(),
+            session: "session-id".to_owned(),
+        }
+    }
+
+    #[test]
+    fn supports_dummy_auth_when_a_flow_is_just_dummy() {
+        assert!(info(vec![vec!["m.login.dummy"]], vec![]).supports_dummy_auth());
+    }
+
+    #[test]
+    fn supports_dummy_auth_when_other_stages_are_already_completed() {
+        assert!(
+            info(vec![vec!["m.login.recaptcha", "m.login.dummy"]], vec!["m.login.recaptcha"])
+                .supports_dummy_auth()
+        );
+    }
+
+    #[test]
+    fn does_not_support_dummy_auth_when_other_stages_remain() {
+        assert!(!info(vec![vec!["m.login.recaptcha", "m.login.dummy"]], vec![]).supports_dummy_auth());
+    }
+
+    #[test]
+    fn as_ruma_carries_kind_and_session_across() {
+        let auth = AuthData::dummy(&info(vec![vec!["m.login.dummy"]], vec![]));
+        let ruma_auth = auth.as_ruma();
+        assert_eq!(ruma_auth.kind, "m.login.dummy");
+        assert_eq!(ruma_auth.session, Some("session-id"));
+    }
+}